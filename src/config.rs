@@ -0,0 +1,238 @@
+use std::fs;
+
+/// User-facing configuration, loaded once from
+/// `$XDG_CONFIG_HOME/rust-dwm-status/config.toml` and shared read-only with
+/// every widget. Any key the user doesn't set falls back to the defaults
+/// this crate has always shipped with.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub separator: String,
+    pub modules: Vec<String>,
+    pub mail: MailConfig,
+    pub volume: VolumeConfig,
+    pub network: NetworkConfig,
+    pub battery: BatteryConfig,
+    pub ram: RamConfig,
+    pub cpu: CpuConfig,
+    pub clock: ClockConfig,
+    pub mqtt: MqttConfig,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            separator: " ⸱ ".to_string(),
+            modules: vec!["mail".to_string(),
+                          "volume".to_string(),
+                          "network".to_string(),
+                          "battery".to_string(),
+                          "ram".to_string(),
+                          "cpu".to_string(),
+                          "clock".to_string()],
+            mail: MailConfig::default(),
+            volume: VolumeConfig::default(),
+            network: NetworkConfig::default(),
+            battery: BatteryConfig::default(),
+            ram: RamConfig::default(),
+            cpu: CpuConfig::default(),
+            clock: ClockConfig::default(),
+            mqtt: MqttConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the user's config file, falling back to defaults when it is
+    /// absent or fails to parse.
+    pub fn load() -> Config {
+        let path = xdg::BaseDirectories::with_prefix("rust-dwm-status")
+            .ok()
+            .and_then(|dirs| dirs.find_config_file("config.toml"));
+
+        let path = match path {
+            Some(path) => path,
+            None => return Config::default(),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                toml::from_str(&contents).unwrap_or_else(|err| {
+                    eprintln!("rust-dwm-status: ignoring {}: {}", path.display(), err);
+                    Config::default()
+                })
+            }
+            Err(_) => Config::default(),
+        }
+    }
+
+    pub fn is_enabled(&self, module: &str) -> bool {
+        self.modules.iter().any(|m| m == module)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MailConfig {
+    pub command: String,
+    pub args: Vec<String>,
+    pub icon: String,
+    /// Segment layout; `{icon}` and `{count}` are substituted. Only
+    /// rendered when the inbox is non-empty.
+    pub format: String,
+    /// How long to wait on `command` before giving up on this tick; a hung
+    /// `notmuch` query shouldn't stall the rest of the bar.
+    pub timeout_ms: u64,
+}
+
+impl Default for MailConfig {
+    fn default() -> MailConfig {
+        MailConfig {
+            command: "notmuch".to_string(),
+            args: vec!["count".to_string(), "tag:inbox".to_string()],
+            icon: "📧".to_string(),
+            format: "{icon} {count}".to_string(),
+            timeout_ms: 2_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct VolumeConfig {
+    pub command: String,
+    pub muted_icon: String,
+    pub low_icon: String,
+    pub medium_icon: String,
+    pub high_icon: String,
+    /// Segment layout when unmuted; `{icon}` (whichever of the icons above
+    /// matches the current level) and `{volume}` are substituted. Muted
+    /// just shows `muted_icon` on its own — there's no level to format.
+    pub format: String,
+    /// `command` is invoked up to twice per tick (mute, then volume); each
+    /// call gets its own budget of this long before it's killed. A timed-out
+    /// mute query is treated as unmuted; a timed-out volume query leaves the
+    /// segment blank.
+    pub timeout_ms: u64,
+}
+
+impl Default for VolumeConfig {
+    fn default() -> VolumeConfig {
+        VolumeConfig {
+            command: "pamixer".to_string(),
+            muted_icon: "🔇".to_string(),
+            low_icon: "🔈".to_string(),
+            medium_icon: "🔉".to_string(),
+            high_icon: "🔊".to_string(),
+            format: "{icon} {volume}".to_string(),
+            timeout_ms: 2_000,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub wired_interface: String,
+    pub wireless_interface: String,
+    pub wired_icon: String,
+    pub wireless_icon: String,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> NetworkConfig {
+        NetworkConfig {
+            wired_interface: "dock0".to_string(),
+            wireless_interface: "wlp58s0".to_string(),
+            wired_icon: "⇅".to_string(),
+            wireless_icon: "📡".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct BatteryConfig {
+    pub plugged_icon: String,
+    pub unplugged_icon: String,
+    /// Segment layout; `{icon}` (whichever of the icons above matches AC
+    /// state) and `{percent}` (one decimal place, no `%`) are substituted.
+    pub format: String,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> BatteryConfig {
+        BatteryConfig {
+            plugged_icon: "🔌".to_string(),
+            unplugged_icon: "🔋".to_string(),
+            format: "{icon} {percent}%".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RamConfig {
+    pub icon: String,
+    /// Segment layout; `{icon}` and `{used}` (formatted byte size, or `_`
+    /// if it couldn't be read) are substituted.
+    pub format: String,
+}
+
+impl Default for RamConfig {
+    fn default() -> RamConfig {
+        RamConfig { icon: "▯".to_string(), format: "{icon} {used}".to_string() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CpuConfig {
+    pub icon: String,
+    /// Segment layout; `{icon}` and `{load}` (1-minute load average to two
+    /// decimal places, or `_` if it couldn't be read) are substituted.
+    pub format: String,
+}
+
+impl Default for CpuConfig {
+    fn default() -> CpuConfig {
+        CpuConfig { icon: "⚙".to_string(), format: "{icon} {load}".to_string() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ClockConfig {
+    pub format: String,
+}
+
+impl Default for ClockConfig {
+    fn default() -> ClockConfig {
+        ClockConfig { format: "📆 %a, %d %h ⸱ 🕓 %R".to_string() }
+    }
+}
+
+/// Off by default: users without a broker shouldn't pay for it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub broker_host: String,
+    pub broker_port: u16,
+    pub client_id: String,
+    pub status_topic: String,
+    pub command_topic: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> MqttConfig {
+        MqttConfig {
+            enabled: false,
+            broker_host: "localhost".to_string(),
+            broker_port: 1883,
+            client_id: "rust-dwm-status".to_string(),
+            status_topic: "home/rust-dwm-status/dwmstatus".to_string(),
+            command_topic: "home/rust-dwm-status/banner".to_string(),
+        }
+    }
+}
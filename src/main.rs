@@ -1,87 +1,138 @@
+// The baseline consistently favors explicit `return`s and `s == ""` checks
+// over their terser equivalents; keep that idiom rather than reformatting
+// every function now that clippy can actually run against this crate.
+#![allow(clippy::needless_return, clippy::comparison_to_empty)]
+
+use std::collections::HashMap;
 use std::error::Error;
-use std::process::Command;
-use std::sync::mpsc;
-use std::time::Duration;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::thread;
 
-#[macro_use]
-extern crate chan;
-extern crate chan_signal;
+extern crate crossbeam_channel;
+extern crate signal_hook;
 
 extern crate chrono;
+#[macro_use]
+extern crate serde_derive;
 extern crate notify_rust;
+extern crate rumqtt;
+extern crate smol;
 extern crate systemstat;
+extern crate toml;
 extern crate xcb;
+extern crate xdg;
+
+mod config;
+mod mqtt;
 
-use chan_signal::Signal;
+use config::Config;
+use crossbeam_channel::{bounded, select, Receiver, Sender};
+use signal_hook::iterator::Signals;
+use signal_hook::{SIGINT, SIGTERM};
 use systemstat::{Platform, System};
 use systemstat::data::IpAddr::V4;
 
-fn get_mail() -> Result<i32, Box<Error>> {
-    let output = Command::new("notmuch")
-        .arg("count")
-        .arg("tag:inbox")
-        .output()?;
+/// Polls `child` on smol's `Timer` until it exits or `deadline` passes, in
+/// which case it's killed rather than abandoned — smol's own cancellation
+/// (dropping a losing `future::or` branch) only detaches the task, it
+/// doesn't touch the subprocess or the thread blocked on it, so a hung
+/// `notmuch`/`pamixer` would otherwise leak both on every timeout.
+async fn wait_with_deadline(mut child: std::process::Child, deadline: Instant) -> Option<std::process::Output> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => return child.wait_with_output().ok(),
+            Ok(None) if Instant::now() < deadline => { smol::Timer::after(Duration::from_millis(50)).await; }
+            Ok(None) => {
+                child.kill().ok();
+                child.wait().ok();
+                return None;
+            }
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Runs `command` on smol, killing it if it's still alive past `timeout`
+/// instead of waiting on it forever. `block_on` ties this to the calling
+/// thread for at most `timeout` — callers that can't afford to block
+/// should run it on a dedicated thread (see `spawn_widget`) rather than
+/// inline on a shared loop.
+fn command_output_with_timeout(command: &str, args: &[String], timeout: Duration) -> Option<std::process::Output> {
+    let child = Command::new(command)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    smol::block_on(wait_with_deadline(child, Instant::now() + timeout))
+}
+
+fn get_mail(cfg: &config::MailConfig) -> Result<i32, Box<dyn Error>> {
+    let timeout = Duration::from_millis(cfg.timeout_ms);
+    let output = command_output_with_timeout(&cfg.command, &cfg.args, timeout)
+        .ok_or_else(|| format!("{} timed out after {:?}", cfg.command, timeout))?;
     let inbox_count_string = String::from_utf8(output.stdout)?;
     return Ok(inbox_count_string.trim().parse()?);
 }
 
-fn mail() -> String {
-    if let Ok(inbox_count) = get_mail() {
+fn mail(cfg: &config::MailConfig) -> String {
+    if let Ok(inbox_count) = get_mail(cfg) {
         if inbox_count > 0 {
-            return format!("📧 {}", inbox_count);
+            return render_template(&cfg.format, &[("icon", &cfg.icon), ("count", &inbox_count.to_string())]);
         }
     }
     return "".to_string();
 }
 
-fn get_mute() -> Result<bool, Box<Error>> {
-    let output = Command::new("pamixer")
-        .arg("--get-mute")
-        .output()?;
+fn get_mute(cfg: &config::VolumeConfig) -> Result<bool, Box<dyn Error>> {
+    let timeout = Duration::from_millis(cfg.timeout_ms);
+    let output = command_output_with_timeout(&cfg.command, &["--get-mute".to_string()], timeout)
+        .ok_or_else(|| format!("{} timed out after {:?}", cfg.command, timeout))?;
     let mute_string = String::from_utf8(output.stdout)?;
-    return Ok(mute_string.trim() == String::from("true"));
+    return Ok(mute_string.trim() == "true");
 }
 
-fn get_volume() -> Result<i32, Box<Error>> {
-    let output = Command::new("pamixer")
-        .arg("--get-volume")
-        .output()?;
+fn get_volume(cfg: &config::VolumeConfig) -> Result<i32, Box<dyn Error>> {
+    let timeout = Duration::from_millis(cfg.timeout_ms);
+    let output = command_output_with_timeout(&cfg.command, &["--get-volume".to_string()], timeout)
+        .ok_or_else(|| format!("{} timed out after {:?}", cfg.command, timeout))?;
     let volume_string = String::from_utf8(output.stdout)?;
     return Ok(volume_string.trim().parse()?);
 }
 
-fn volume() -> String {
-    if let Ok(muted) = get_mute() {
+fn volume(cfg: &config::VolumeConfig) -> String {
+    if let Ok(muted) = get_mute(cfg) {
         if muted {
-            return "🔇".to_string()
+            return cfg.muted_icon.clone()
         }
     }
 
-    if let Ok(volume) = get_volume() {
+    if let Ok(volume) = get_volume(cfg) {
         let speaker = match volume {
-            0 ... 33 => "🔈",
-            34 ... 66 => "🔉",
-            _ => "🔊",
+            0 ..= 33 => &cfg.low_icon,
+            34 ..= 66 => &cfg.medium_icon,
+            _ => &cfg.high_icon,
         };
-        return format!("{} {}", speaker, volume)
+        return render_template(&cfg.format, &[("icon", speaker), ("volume", &volume.to_string())]);
     }
     return "".to_string();
 }
 
-fn network(sys: &System) -> String {
+fn network(sys: &System, cfg: &config::NetworkConfig) -> String {
     if let Ok(interfaces) = sys.networks() {
-        if let Some(dock_info) = interfaces.get("dock0") {
+        if let Some(dock_info) = interfaces.get(&cfg.wired_interface) {
             for net in &dock_info.addrs {
                 if let V4(_) = net.addr {
-                    return "⇅".to_string()
+                    return cfg.wired_icon.clone()
                 }
             }
         }
-        if let Some(wireless_info) = interfaces.get("wlp58s0") {
+        if let Some(wireless_info) = interfaces.get(&cfg.wireless_interface) {
             for net in &wireless_info.addrs {
                 if let V4(_) = net.addr {
-                    return "📡".to_string()
+                    return cfg.wireless_icon.clone()
                 }
             }
         }
@@ -91,134 +142,331 @@ fn network(sys: &System) -> String {
     }
 }
 
-fn plugged(sys: &System) -> String {
+fn plugged(sys: &System, cfg: &config::BatteryConfig) -> String {
     if let Ok(plugged) = sys.on_ac_power() {
         if plugged {
-            "🔌".to_string()
+            cfg.plugged_icon.clone()
         } else {
-            "🔋".to_string()
+            cfg.unplugged_icon.clone()
         }
     } else {
-        "🔌".to_string()
+        cfg.plugged_icon.clone()
     }
 }
 
-fn battery(sys: &System) -> String {
+fn battery(sys: &System, cfg: &config::BatteryConfig) -> String {
     if let Ok(bat) = sys.battery_life() {
-        format!("{} {:.1}%", plugged(sys), bat.remaining_capacity * 100.)
+        let percent = format!("{:.1}", bat.remaining_capacity * 100.);
+        render_template(&cfg.format, &[("icon", &plugged(sys, cfg)), ("percent", &percent)])
     } else {
         "".to_string()
     }
 }
 
-fn ram(sys: &System) -> String {
-    if let Ok(mem) = sys.memory() {
-        let used = mem.total - mem.free;
-        format!("▯ {}", used)
-    } else {
-        "▯ _".to_string()
+fn ram(sys: &System, cfg: &config::RamConfig) -> String {
+    let used = match sys.memory() {
+        Ok(mem) => systemstat::saturating_sub_bytes(mem.total, mem.free).to_string(),
+        Err(_) => "_".to_string(),
+    };
+    render_template(&cfg.format, &[("icon", &cfg.icon), ("used", &used)])
+}
+
+fn cpu(sys: &System, cfg: &config::CpuConfig) -> String {
+    let load = match sys.load_average() {
+        Ok(load) => format!("{:.2}", load.one),
+        Err(_) => "_".to_string(),
+    };
+    render_template(&cfg.format, &[("icon", &cfg.icon), ("load", &load)])
+}
+
+fn date(cfg: &config::ClockConfig) -> String {
+    chrono::Local::now().format(&cfg.format).to_string()
+}
+
+/// Fills `{name}` placeholders in `template` from `values`, in order. Used
+/// by every module whose segment is more than a single icon swap, so users
+/// can rearrange or drop pieces (e.g. `"{icon}{count}"` with no space)
+/// instead of being stuck with this crate's hard-coded layout.
+fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut result = template.to_string();
+    for (name, value) in values {
+        result = result.replace(&format!("{{{}}}", name), value);
     }
+    result
 }
 
-fn cpu(sys: &System) -> String {
-    if let Ok(load) = sys.load_average() {
-        format!("⚙ {:.2}", load.one)
-    } else {
-        "⚙ _".to_string()
+fn separated(s: String, separator: &str) -> String {
+    if s == "" { s } else { s + separator }
+}
+
+/// Last-rendered segment for each widget, keyed by module name, so a tick
+/// for one group doesn't force the others to be recomputed. Assembly reads
+/// `config.modules` to decide which modules run at all, and in what order.
+struct WidgetCache {
+    segments: HashMap<String, String>,
+}
+
+impl WidgetCache {
+    fn new() -> WidgetCache {
+        WidgetCache { segments: HashMap::new() }
+    }
+
+    fn set(&mut self, module: &str, value: String) {
+        self.segments.insert(module.to_string(), value);
+    }
+
+    fn assemble(&self, cfg: &Config) -> String {
+        let mut result = String::new();
+        let modules = &cfg.modules;
+        for (i, module) in modules.iter().enumerate() {
+            let value = self.segments.get(module).cloned().unwrap_or_default();
+            if i + 1 == modules.len() {
+                result += &value;
+            } else {
+                result += &separated(value, &cfg.separator);
+            }
+        }
+        result
     }
 }
 
-fn date() -> String {
-    chrono::Local::now().format("📆 %a, %d %h ⸱ 🕓 %R").to_string()
+enum XMessage {
+    Status(String),
+    Shutdown,
 }
 
-fn separated(s: String) -> String {
-    if s == "" { s } else { s + " ⸱ " }
+fn get_wm_name(xconn: &xcb::Connection, root_window: xcb::Window) -> Option<String> {
+    let cookie = xcb::xproto::get_property(xconn,
+                                           false,
+                                           root_window,
+                                           xcb::xproto::ATOM_WM_NAME,
+                                           xcb::xproto::ATOM_STRING,
+                                           0,
+                                           1024);
+    let reply = cookie.get_reply().ok()?;
+    String::from_utf8(reply.value().to_vec()).ok()
 }
 
-fn status(sys: &System) -> String {
-    separated(mail()) +
-        &separated(volume()) +
-        &separated(network(sys)) +
-        &separated(battery(sys)) +
-        &separated(ram(sys)) +
-        &separated(cpu(sys)) +
-        &date()
+fn set_wm_name(xconn: &xcb::Connection, root_window: xcb::Window, name: &str) {
+    xcb::xproto::change_property(xconn,
+                                 xcb::xproto::PROP_MODE_REPLACE as u8,
+                                 root_window,
+                                 xcb::xproto::ATOM_WM_NAME,
+                                 xcb::xproto::ATOM_STRING,
+                                 8,
+                                 name.as_bytes());
+    xconn.flush();
 }
 
-fn run_update_status(chan: mpsc::Receiver<String>) {
+fn run_update_status(rx_status: Receiver<XMessage>) {
     let (xconn, screen_num) = xcb::Connection::connect(None).unwrap();
     let setup = xconn.get_setup();
     let screen = setup.roots().nth(screen_num as usize).unwrap();
     let root_window = screen.root();
 
-    for status in chan.iter() {
-        xcb::xproto::change_property(&xconn,
-                                     xcb::xproto::PROP_MODE_REPLACE as u8,
-                                     root_window,
-                                     xcb::xproto::ATOM_WM_NAME,
-                                     xcb::xproto::ATOM_STRING,
-                                     8,
-                                     status.as_bytes());
-        xconn.flush();
+    // Captured so a shutdown can put the bar back the way it found it.
+    let original_name = get_wm_name(&xconn, root_window);
+
+    for message in rx_status.iter() {
+        match message {
+            XMessage::Status(status) => set_wm_name(&xconn, root_window, &status),
+            XMessage::Shutdown => {
+                if let Some(name) = &original_name {
+                    set_wm_name(&xconn, root_window, name);
+                }
+                break;
+            }
+        }
     }
 }
 
-fn run(_sdone: chan::Sender<()>, tx_status: mpsc::Sender<String>) {
+/// Runs `compute` on its own thread every `interval`, feeding each result
+/// back over the returned channel. Widgets whose `compute` can block on a
+/// subprocess (mail, volume) use this instead of running inline on `run`'s
+/// select loop, so a hung command only ever stalls its own thread.
+fn spawn_widget<F>(interval: Duration, compute: F) -> Receiver<String>
+    where F: Fn() -> String + Send + 'static
+{
+    let (tx, rx) = bounded(1);
+    thread::spawn(move || {
+        for _ in crossbeam_channel::tick(interval).iter() {
+            if tx.send(compute()).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn run(cfg: Arc<Config>,
+       _tx_done: Sender<()>,
+       tx_status: Sender<XMessage>,
+       tx_mqtt_status: Sender<String>,
+       mut rx_mqtt_banner: Receiver<String>,
+       rx_shutdown: Receiver<()>) {
     use notify_rust::server::NotificationServer;
-    let mut server = NotificationServer::new();
+    let server = NotificationServer::create();
     let sys = System::new();
 
-    let (tx_notification, rx_notification) = std::sync::mpsc::channel();
+    let (tx_notification, rx_notification) = crossbeam_channel::unbounded();
     thread::spawn(move || {
-                           server.start(|notification| tx_notification.send(notification.clone()).unwrap())
-                       });
-    let mut banner = String::new();
+        NotificationServer::start(&server, move |notification| tx_notification.send(notification.clone()).unwrap())
+    });
+
+    // Each group is only as fresh as it needs to be. Mail and volume run on
+    // their own threads (see `spawn_widget`) rather than ticking this loop
+    // directly, since a hung `notmuch` or `pamixer` call must never stall
+    // the clock or anything else this loop handles.
+    let clock_tick = crossbeam_channel::tick(Duration::from_secs(1));
+    let system_tick = crossbeam_channel::tick(Duration::from_secs(5));
+
+    let rx_mail = if cfg.is_enabled("mail") {
+        let mail_cfg = cfg.mail.clone();
+        spawn_widget(Duration::from_secs(10), move || mail(&mail_cfg))
+    } else {
+        crossbeam_channel::never()
+    };
+    let rx_volume = if cfg.is_enabled("volume") {
+        let volume_cfg = cfg.volume.clone();
+        spawn_widget(Duration::from_secs(10), move || volume(&volume_cfg))
+    } else {
+        crossbeam_channel::never()
+    };
+
+    let mut cache = WidgetCache::new();
+    if cfg.is_enabled("mail") { cache.set("mail", mail(&cfg.mail)); }
+    if cfg.is_enabled("volume") { cache.set("volume", volume(&cfg.volume)); }
+    if cfg.is_enabled("network") { cache.set("network", network(&sys, &cfg.network)); }
+    if cfg.is_enabled("battery") { cache.set("battery", battery(&sys, &cfg.battery)); }
+    if cfg.is_enabled("ram") { cache.set("ram", ram(&sys, &cfg.ram)); }
+    if cfg.is_enabled("cpu") { cache.set("cpu", cpu(&sys, &cfg.cpu)); }
+    if cfg.is_enabled("clock") { cache.set("clock", date(&cfg.clock)); }
+
+    let mut banner: Option<String> = None;
+    let mut banner_expires = Instant::now();
+    let mut last_sent = String::new();
+
     loop {
-        let received = rx_notification.try_recv();
-        if received.is_ok() {
-            let notification = received.unwrap();
-            banner = format!("{} {}", notification.summary, notification.body);
-            tx_status.send(banner.clone()).unwrap();
-            let max_timeout = 10_000; // milliseconds (1 minute)
-            let mut t = notification.timeout.into();
-            if t > max_timeout || t < 0 {
-                t = max_timeout;
-            }
-            thread::sleep(Duration::from_millis(t as u64));
+        select! {
+            recv(clock_tick) -> _ => {
+                if cfg.is_enabled("clock") { cache.set("clock", date(&cfg.clock)); }
+                if banner.is_some() && Instant::now() >= banner_expires {
+                    banner = None;
+                }
+            },
+            recv(system_tick) -> _ => {
+                if cfg.is_enabled("network") { cache.set("network", network(&sys, &cfg.network)); }
+                if cfg.is_enabled("battery") { cache.set("battery", battery(&sys, &cfg.battery)); }
+                if cfg.is_enabled("ram") { cache.set("ram", ram(&sys, &cfg.ram)); }
+                if cfg.is_enabled("cpu") { cache.set("cpu", cpu(&sys, &cfg.cpu)); }
+            },
+            recv(rx_mail) -> segment => {
+                if let Ok(segment) = segment { cache.set("mail", segment); }
+            },
+            recv(rx_volume) -> segment => {
+                if let Ok(segment) = segment { cache.set("volume", segment); }
+            },
+            recv(rx_notification) -> notification => {
+                let notification = notification.unwrap();
+                let max_timeout = 10_000; // milliseconds (10 seconds)
+                let mut t = notification.timeout.into();
+                if t > max_timeout || t < 0 {
+                    t = max_timeout;
+                }
+                banner = Some(format!("{} {}", notification.summary, notification.body));
+                banner_expires = Instant::now() + Duration::from_millis(t as u64);
+            },
+            recv(rx_mqtt_banner) -> banner_text => {
+                match banner_text {
+                    Ok(banner_text) => {
+                        banner = Some(banner_text);
+                        banner_expires = Instant::now() + Duration::from_secs(10);
+                    }
+                    // The MQTT thread gave up (e.g. the broker is
+                    // unreachable) and dropped its sender. A disconnected
+                    // recv() never blocks again, so without this the arm
+                    // would spin forever; fall back to a channel that never
+                    // fires instead, same as a disabled widget.
+                    Err(_) => rx_mqtt_banner = crossbeam_channel::never(),
+                }
+            },
+            recv(rx_shutdown) -> _ => break,
         }
-        let next_banner = status(&sys);
-        if next_banner != banner {
-            banner = next_banner;
-            tx_status.send(banner.clone()).unwrap();
+
+        let next_status = banner.clone().unwrap_or_else(|| cache.assemble(&cfg));
+        if next_status != last_sent {
+            last_sent = next_status.clone();
+            tx_mqtt_status.send(next_status.clone()).ok();
+            tx_status.send(XMessage::Status(next_status)).unwrap();
         }
-        thread::sleep(Duration::from_millis(500));
     }
 }
 
 fn main() {
+    let cfg = Arc::new(Config::load());
+
     // Signal gets a value when the OS sent a INT or TERM signal.
-    let signal = chan_signal::notify(&[Signal::INT, Signal::TERM]);
+    let signals = Signals::new([SIGINT, SIGTERM]).expect("failed to register signal handlers");
+    let (tx_signal, rx_signal) = bounded(0);
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if tx_signal.send(signal).is_err() {
+                break;
+            }
+        }
+    });
 
-    // When our work is complete, send a sentinel value on `sdone`.
-    let (sdone, rdone) = chan::sync(0);
+    // When our work is complete, `run` drops `tx_done`, which wakes `rx_done`.
+    let (tx_done, rx_done) = bounded::<()>(0);
+
+    // Dropped once shutdown begins, so `run`'s select loop wakes up and
+    // returns; the widget threads stop once `run` has dropped their receivers.
+    let (tx_shutdown, rx_shutdown) = bounded::<()>(0);
 
     // Channel to pass status updates
-    let (tx_status, rx_status) = mpsc::channel();
+    let (tx_status, rx_status) = crossbeam_channel::unbounded();
 
-    thread::spawn(move || run_update_status(rx_status));
+    let xorg_thread = thread::spawn(move || run_update_status(rx_status));
+
+    // MQTT bridges the assembled status out, and remote banners in; the
+    // channels exist unconditionally so `run`'s select loop needs no
+    // special-casing, but the broker thread only starts when configured.
+    let (tx_mqtt_status, rx_mqtt_status) = crossbeam_channel::unbounded();
+    let (tx_mqtt_banner, rx_mqtt_banner) = crossbeam_channel::unbounded();
+    if cfg.mqtt.enabled {
+        let mqtt_cfg = cfg.mqtt.clone();
+        thread::spawn(move || mqtt::run(mqtt_cfg, rx_mqtt_status, tx_mqtt_banner));
+    } else {
+        // Otherwise `rx_mqtt_status` would sit here unread for the life of
+        // the process, and since crossbeam only errors a send once every
+        // receiver is gone, `run`'s `tx_mqtt_status.send(...)` would queue
+        // forever.
+        drop(rx_mqtt_status);
+    }
 
     // Run work.
     let main_tx_status = tx_status.clone();
-    thread::spawn(move || run(sdone, main_tx_status));
+    let run_rx_shutdown = rx_shutdown.clone();
+    let run_cfg = cfg.clone();
+    let run_thread = thread::spawn(move || {
+        run(run_cfg, tx_done, main_tx_status, tx_mqtt_status, rx_mqtt_banner, run_rx_shutdown)
+    });
 
     // Wait for a signal or for work to be done.
-    chan_select! {
-        signal.recv() -> signal => {
-            tx_status.send(format!("rust-dwm-status stopped with signal {:?}.", signal)).unwrap();
+    select! {
+        recv(rx_signal) -> signal => {
+            let signal = signal.unwrap();
+            tx_status.send(XMessage::Status(format!("rust-dwm-status stopped with signal {}.", signal))).ok();
         },
-        rdone.recv() => {
-            tx_status.send("rust-dwm-status: done.".to_string()).unwrap();
+        recv(rx_done) -> _ => {
+            tx_status.send(XMessage::Status("rust-dwm-status: done.".to_string())).ok();
         }
     }
+
+    // Restore the window's original name and let the worker threads exit before we do.
+    tx_status.send(XMessage::Shutdown).ok();
+    drop(tx_shutdown);
+    drop(rx_shutdown);
+    run_thread.join().expect("status thread panicked");
+    xorg_thread.join().expect("xorg update thread panicked");
 }
@@ -0,0 +1,49 @@
+use std::thread;
+
+use crossbeam_channel::{Receiver, Sender};
+use rumqtt::{MqttClient, MqttOptions, Notification, QoS};
+
+use crate::config::MqttConfig;
+
+/// Bridges the bar to an MQTT broker: publishes every assembled status line
+/// on `cfg.status_topic`, and forwards anything published on
+/// `cfg.command_topic` to `tx_banner`, which `run` turns into a banner the
+/// same way it does desktop notifications. Only spawned when `cfg.enabled`
+/// is set.
+///
+/// A broker that's unreachable or rejects the subscription is a config/
+/// network hiccup, not a crash: this returns early (dropping `tx_banner`
+/// and `rx_status`) instead of panicking, so the bar just runs as if MQTT
+/// were disabled rather than taking the whole process down with it.
+pub fn run(cfg: MqttConfig, rx_status: Receiver<String>, tx_banner: Sender<String>) {
+    let options = MqttOptions::new(cfg.client_id.as_str(), cfg.broker_host.as_str(), cfg.broker_port);
+    let (mut client, notifications) = match MqttClient::start(options) {
+        Ok(connection) => connection,
+        Err(err) => {
+            eprintln!("rust-dwm-status: failed to connect to MQTT broker: {}", err);
+            return;
+        }
+    };
+
+    if let Err(err) = client.subscribe(&cfg.command_topic, QoS::AtLeastOnce) {
+        eprintln!("rust-dwm-status: failed to subscribe to MQTT command topic: {}", err);
+        return;
+    }
+
+    let command_topic = cfg.command_topic.clone();
+    thread::spawn(move || {
+        for notification in notifications {
+            if let Notification::Publish(publish) = notification {
+                if *publish.topic_name == command_topic {
+                    if let Ok(banner) = String::from_utf8(publish.payload.to_vec()) {
+                        tx_banner.send(banner).ok();
+                    }
+                }
+            }
+        }
+    });
+
+    for status in rx_status.iter() {
+        client.publish(&cfg.status_topic, QoS::AtMostOnce, false, status.into_bytes()).ok();
+    }
+}